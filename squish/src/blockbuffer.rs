@@ -0,0 +1,196 @@
+// Copyright (c) 2006 Simon Brown <si@sjbrown.co.uk>
+// Copyright (c) 2018-2021 Jan Solanti <jhs@psonet.com>
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to	deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+// SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::RawColor4x4Block;
+
+const BLOCK_SIZE: usize = 4;
+const NUMBER_OF_COLOR_CHANNELS: usize = 4;
+
+/// A plain in-memory 4x4 RGBA block that implements [`RawColor4x4Block`],
+/// suitable for handing off to a compressor.
+#[derive(Clone, Copy, Default)]
+pub struct RawColor4x4Buffer {
+    texels: [[u8; NUMBER_OF_COLOR_CHANNELS]; 16],
+}
+
+impl RawColor4x4Buffer {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads back the RGBA texel at `(x, y)` within the block.
+    pub fn texel(&self, x: usize, y: usize) -> [u8; NUMBER_OF_COLOR_CHANNELS] {
+        self.texels[y * BLOCK_SIZE + x]
+    }
+}
+
+impl RawColor4x4Block for RawColor4x4Buffer {
+    fn number_of_channels(&self) -> usize {
+        NUMBER_OF_COLOR_CHANNELS
+    }
+
+    fn set_value(&mut self, x: usize, y: usize, channel: usize, value: u8) {
+        self.texels[y * BLOCK_SIZE + x][channel] = value;
+    }
+}
+
+/// Accumulates any number of whole scanlines of RGBA pixels into 4x4
+/// texel blocks, so that callers can feed decoded image data straight
+/// off a decoder without pre-tiling it into exact 4x4 chunks first.
+///
+/// Pixels are consumed left to right, top to bottom. Scanlines of a band
+/// that is not yet complete are held between calls to
+/// [`push_rows`](Self::push_rows) until a full band of tiles (4
+/// scanlines) is available; a partial scanline is rejected. Edge tiles that would run
+/// past the image's width or height are padded by replicating the last
+/// valid texel in each direction (edge replication), so the packed 565
+/// endpoints aren't skewed by garbage data.
+pub struct BlockBuffer {
+    image_width: usize,
+    image_height: usize,
+    cursor_y: usize,
+    pending_rows: Vec<Vec<[u8; NUMBER_OF_COLOR_CHANNELS]>>,
+}
+
+impl BlockBuffer {
+    /// Creates a new buffer for an image of the given pixel dimensions.
+    pub fn new(image_width: usize, image_height: usize) -> Self {
+        assert!(image_width > 0 && image_height > 0);
+        Self {
+            image_width,
+            image_height,
+            cursor_y: 0,
+            pending_rows: Vec::with_capacity(BLOCK_SIZE),
+        }
+    }
+
+    /// The (x, y) texel position at which the next pushed scanline
+    /// starts. Scanlines are pushed whole, so x is always 0.
+    pub fn cursor(&self) -> (usize, usize) {
+        (0, self.cursor_y)
+    }
+
+    /// Feeds one or more full scanlines of RGBA pixels. `pixels` must hold
+    /// whole scanlines (`width * 4` bytes each) and `width` must match the
+    /// buffer's configured image width. Returns the 4x4 blocks completed
+    /// by this call, in left-to-right order for each completed band.
+    pub fn push_rows(&mut self, pixels: &[u8], width: usize) -> Vec<RawColor4x4Buffer> {
+        assert_eq!(width, self.image_width);
+        let stride = width * NUMBER_OF_COLOR_CHANNELS;
+        assert_eq!(pixels.len() % stride, 0);
+
+        let mut completed = Vec::new();
+        for scanline in pixels.chunks_exact(stride) {
+            assert!(self.cursor_y < self.image_height, "pushed past image height");
+
+            let mut texels = Vec::with_capacity(width);
+            for texel in scanline.chunks_exact(NUMBER_OF_COLOR_CHANNELS) {
+                let mut value = [0u8; NUMBER_OF_COLOR_CHANNELS];
+                value.copy_from_slice(texel);
+                texels.push(value);
+            }
+            self.pending_rows.push(texels);
+            self.cursor_y += 1;
+
+            if self.pending_rows.len() == BLOCK_SIZE {
+                completed.extend(self.drain_tile_band());
+            }
+        }
+        completed
+    }
+
+    /// Flushes any remaining buffered scanlines as padded edge tiles.
+    /// Consumes the buffer, since no more rows can follow a flush.
+    pub fn finish(mut self) -> Vec<RawColor4x4Buffer> {
+        if self.pending_rows.is_empty() {
+            Vec::new()
+        } else {
+            self.drain_tile_band()
+        }
+    }
+
+    /// Turns the currently buffered scanlines (1 to 4 of them) into a
+    /// full band of 4x4 tiles spanning the image's width, padding both
+    /// axes by clamping to the last valid texel.
+    fn drain_tile_band(&mut self) -> Vec<RawColor4x4Buffer> {
+        let valid_rows = self.pending_rows.len();
+        let number_of_tiles = self.image_width.div_ceil(BLOCK_SIZE);
+        let mut tiles = Vec::with_capacity(number_of_tiles);
+
+        let mut x = 0;
+        while x < self.image_width {
+            let mut block = RawColor4x4Buffer::new();
+            for by in 0..BLOCK_SIZE {
+                let row = &self.pending_rows[by.min(valid_rows - 1)];
+                for bx in 0..BLOCK_SIZE {
+                    let texel = row[(x + bx).min(self.image_width - 1)];
+                    for (channel, value) in texel.into_iter().enumerate() {
+                        block.set_value(bx, by, channel, value);
+                    }
+                }
+            }
+            tiles.push(block);
+            x += BLOCK_SIZE;
+        }
+
+        self.pending_rows.clear();
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5x3 image (not a multiple of 4 in either dimension) should
+    /// still produce one band of two tiles, with the rightmost/bottommost
+    /// edge texels replicated to fill the padding.
+    #[test]
+    fn pads_edge_tiles_by_replicating_the_last_valid_texel() {
+        let width = 5;
+        let height = 3;
+        let mut buffer = BlockBuffer::new(width, height);
+
+        let mut pixels = Vec::with_capacity(width * height * NUMBER_OF_COLOR_CHANNELS);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&[x as u8, y as u8, 0, 255]);
+            }
+        }
+
+        let mut tiles = buffer.push_rows(&pixels, width);
+        tiles.extend(buffer.finish());
+
+        // width 5 needs 2 tile columns, height 3 needs 1 tile row.
+        assert_eq!(tiles.len(), 2);
+
+        let last_column_tile = &tiles[1];
+        // Column 4 is the last real column; columns 5..8 of the tile
+        // pad by replicating it.
+        for bx in 1..4 {
+            assert_eq!(last_column_tile.texel(bx, 0)[0], 4);
+        }
+        // Row 2 is the last real row; row 3 of the tile pads by
+        // replicating it.
+        assert_eq!(last_column_tile.texel(0, 3)[1], 2);
+    }
+}
@@ -20,7 +20,7 @@
 // TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
 // SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
-use core::{mem, u8};
+use core::mem;
 
 use crate::{
     math::{f32_to_i32_clamped, Vec3},
@@ -96,6 +96,318 @@ pub fn write4(start: &Vec3, end: &Vec3, indices: &[u8; 16], block: &mut [u8]) {
     write_block(a, b, &remapped, block);
 }
 
+/// Per-channel Floyd-Steinberg error diffusion state for dithered 565
+/// quantization, carried across every block of an image so that the
+/// rounding error of one block's endpoints is spread onto its neighbours
+/// instead of all rounding the same direction, which reduces visible
+/// banding on smooth gradients at no extra storage cost.
+///
+/// Blocks must be fed in left-to-right, top-to-bottom scan order via
+/// [`write3_dithered`]/[`write4_dithered`] for the diffusion to line up
+/// with the image layout.
+pub struct DitherState {
+    block_width: usize,
+    cursor: usize,
+    current_row: Vec<[f32; 3]>,
+    next_row: Vec<[f32; 3]>,
+}
+
+impl DitherState {
+    /// Creates a new diffusion state for an image that is `block_width`
+    /// 4x4 blocks wide.
+    pub fn new(block_width: usize) -> Self {
+        assert!(block_width > 0);
+        Self {
+            block_width,
+            cursor: 0,
+            current_row: vec![[0.0; 3]; block_width + 1],
+            next_row: vec![[0.0; 3]; block_width + 1],
+        }
+    }
+
+    fn carried_in(&self) -> [f32; 3] {
+        self.current_row[self.cursor]
+    }
+
+    /// Diffuses one block's quantization error onto its right,
+    /// bottom-left, bottom and bottom-right neighbours using the classic
+    /// Floyd-Steinberg weights (7/16, 3/16, 5/16, 1/16), then advances
+    /// the cursor to the next block in scan order.
+    ///
+    /// Must be called exactly once per block: both endpoints of a block
+    /// read the same carried-in error and contribute to a single
+    /// diffusion step, so that the cursor (and the row wrap at
+    /// `block_width` blocks) stays in lockstep with the image layout.
+    fn diffuse(&mut self, error: [f32; 3]) {
+        let x = self.cursor;
+        for (channel, e) in error.into_iter().enumerate() {
+            self.current_row[x + 1][channel] += e * 7.0 / 16.0;
+            if x > 0 {
+                self.next_row[x - 1][channel] += e * 3.0 / 16.0;
+            }
+            self.next_row[x][channel] += e * 5.0 / 16.0;
+            self.next_row[x + 1][channel] += e * 1.0 / 16.0;
+        }
+
+        self.cursor += 1;
+        if self.cursor == self.block_width {
+            self.cursor = 0;
+            self.current_row =
+                mem::replace(&mut self.next_row, vec![[0.0; 3]; self.block_width + 1]);
+        }
+    }
+}
+
+/// Averages the two endpoints' quantization residuals into the single
+/// per-channel error a block diffuses to its neighbours.
+fn average_error(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0]
+}
+
+/// Like [`pack_565`], but quantizes against a carried-in per-channel
+/// error instead of the raw colour, and returns the residual alongside
+/// the packed value so the caller can diffuse it. Does not itself touch
+/// `DitherState`'s cursor - see [`DitherState::diffuse`].
+fn pack_565_dithered(colour: &Vec3, carried: [f32; 3]) -> (u16, [f32; 3]) {
+    let r_in = 31.0 * colour.x() + carried[0];
+    let g_in = 63.0 * colour.y() + carried[1];
+    let b_in = 31.0 * colour.z() + carried[2];
+
+    let r = f32_to_i32_clamped(r_in, 31) as u16;
+    let g = f32_to_i32_clamped(g_in, 63) as u16;
+    let b = f32_to_i32_clamped(b_in, 31) as u16;
+
+    let residual = [r_in - r as f32, g_in - g as f32, b_in - b as f32];
+    ((r << 11) | (g << 5) | b, residual)
+}
+
+/// Dithered variant of [`write3`]. Pass the same [`DitherState`] to every
+/// block of an image, in left-to-right, top-to-bottom scan order, so
+/// quantization error diffuses consistently across the whole image.
+pub fn write3_dithered(
+    start: &Vec3,
+    end: &Vec3,
+    indices: &[u8; 16],
+    block: &mut [u8],
+    dither: &mut DitherState,
+) {
+    let carried = dither.carried_in();
+    let (mut a, error_a) = pack_565_dithered(start, carried);
+    let (mut b, error_b) = pack_565_dithered(end, carried);
+    dither.diffuse(average_error(error_a, error_b));
+
+    let mut remapped = *indices;
+
+    if a > b {
+        // swap a, b and indices referring to them
+        mem::swap(&mut a, &mut b);
+        for index in &mut remapped[..] {
+            *index = match *index {
+                0 => 1,
+                1 => 0,
+                x => x,
+            };
+        }
+    }
+
+    write_block(a, b, &remapped, block);
+}
+
+/// Dithered variant of [`write4`]. Pass the same [`DitherState`] to every
+/// block of an image, in left-to-right, top-to-bottom scan order, so
+/// quantization error diffuses consistently across the whole image.
+pub fn write4_dithered(
+    start: &Vec3,
+    end: &Vec3,
+    indices: &[u8; 16],
+    block: &mut [u8],
+    dither: &mut DitherState,
+) {
+    let carried = dither.carried_in();
+    let (mut a, error_a) = pack_565_dithered(start, carried);
+    let (mut b, error_b) = pack_565_dithered(end, carried);
+    dither.diffuse(average_error(error_a, error_b));
+
+    let mut remapped = [0u8; 16];
+    if a < b {
+        mem::swap(&mut a, &mut b);
+        for (remapped, index) in remapped.iter_mut().zip(indices) {
+            *remapped = (index ^ 0x01) & 0x03;
+        }
+    } else if a > b {
+        remapped = *indices;
+    }
+
+    write_block(a, b, &remapped, block);
+}
+
+#[cfg(test)]
+mod dither_tests {
+    use super::*;
+
+    /// The diffusion cursor must wrap (and swap in the carried-in row
+    /// for the next scanline of blocks) after exactly `block_width`
+    /// blocks, not after `block_width` calls to `pack_565_dithered`
+    /// (there are two such calls - one per endpoint - per block).
+    #[test]
+    fn row_wraps_after_block_width_blocks_not_half_that() {
+        let block_width = 5;
+        let mut dither = DitherState::new(block_width);
+        let colour = Vec3::new(0.5, 0.5, 0.5);
+        let indices = [0u8; 16];
+        let mut block = [0u8; 8];
+
+        for blocks_written in 1..=block_width {
+            write3_dithered(&colour, &colour, &indices, &mut block, &mut dither);
+            if blocks_written < block_width {
+                assert_ne!(
+                    dither.cursor, 0,
+                    "cursor wrapped after only {blocks_written} of {block_width} blocks",
+                );
+            }
+        }
+        assert_eq!(
+            dither.cursor, 0,
+            "cursor should have wrapped after a full row of {block_width} blocks",
+        );
+
+        // The second row's first block starts from column 0 again, so
+        // writing it advances the cursor to column 1.
+        write3_dithered(&colour, &colour, &indices, &mut block, &mut dither);
+        assert_eq!(dither.cursor, 1);
+    }
+}
+
+/// Encodes a 4x4 RGBA block using BC1's implicit 1-bit "punchthrough"
+/// alpha mode: texels whose alpha is below `alpha_threshold` become
+/// fully transparent, and the two 565 endpoints are fit only over the
+/// remaining opaque texels.
+///
+/// This relies on [`write3`] always keeping the endpoint ordering with
+/// `a <= b`, which selects BC1's 3-colour + transparent-black mode, and
+/// on it never remapping LUT index 3 away from the transparent slot
+/// while reordering endpoints.
+///
+/// The endpoints themselves are fit with a simple per-channel min/max
+/// bounding box over the opaque texels, not a real least-squares or
+/// principal-axis fit; this trades some quality for simplicity and is a
+/// reasonable first cut to replace later if a proper colour-fit module
+/// exists elsewhere in the crate.
+pub fn write3_punchthrough(rgba: &[[u8; 4]; 16], alpha_threshold: u8, block: &mut [u8]) {
+    let to_colour = |texel: &[u8; 4]| {
+        Vec3::new(
+            texel[0] as f32 / 255.0,
+            texel[1] as f32 / 255.0,
+            texel[2] as f32 / 255.0,
+        )
+    };
+
+    let opaque: Vec<Vec3> = rgba
+        .iter()
+        .filter(|texel| texel[3] >= alpha_threshold)
+        .map(to_colour)
+        .collect();
+
+    if opaque.is_empty() {
+        // Fully transparent block: endpoints don't matter since every
+        // texel maps to the transparent slot.
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        write3(&zero, &zero, &[3u8; 16], block);
+        return;
+    }
+
+    let mut min = opaque[0];
+    let mut max = opaque[0];
+    for colour in &opaque[1..] {
+        min = Vec3::new(
+            min.x().min(colour.x()),
+            min.y().min(colour.y()),
+            min.z().min(colour.z()),
+        );
+        max = Vec3::new(
+            max.x().max(colour.x()),
+            max.y().max(colour.y()),
+            max.z().max(colour.z()),
+        );
+    }
+
+    let start = max;
+    let end = min;
+    let midpoint = Vec3::new(
+        (start.x() + end.x()) / 2.0,
+        (start.y() + end.y()) / 2.0,
+        (start.z() + end.z()) / 2.0,
+    );
+    let candidates = [start, end, midpoint];
+
+    let mut indices = [3u8; 16];
+    for (i, texel) in rgba.iter().enumerate() {
+        if texel[3] < alpha_threshold {
+            continue;
+        }
+
+        let colour = to_colour(texel);
+        let mut best_index = 0usize;
+        let mut best_distance = f32::MAX;
+        for (candidate_index, candidate) in candidates.iter().enumerate() {
+            let dx = colour.x() - candidate.x();
+            let dy = colour.y() - candidate.y();
+            let dz = colour.z() - candidate.z();
+            let distance = dx * dx + dy * dy + dz * dz;
+            if distance < best_distance {
+                best_distance = distance;
+                best_index = candidate_index;
+            }
+        }
+        indices[i] = best_index as u8;
+    }
+
+    write3(&start, &end, &indices, block);
+}
+
+#[cfg(test)]
+mod punchthrough_tests {
+    use super::*;
+    use crate::RawColor4x4Buffer;
+
+    /// Every texel at or above the alpha threshold must decode back to
+    /// full alpha, and every texel below it must decode to transparent
+    /// black, regardless of how the opaque texels' colours happen to
+    /// order the two endpoints.
+    #[test]
+    fn preserves_the_transparent_slot_through_endpoint_reordering() {
+        let mut rgba = [[0u8, 0, 0, 0]; 16];
+        for (i, texel) in rgba.iter_mut().enumerate() {
+            if i % 2 == 0 {
+                // Opaque, with colours spanning both ends of the 565
+                // range so endpoint reordering in `write3` is exercised.
+                let v = (i * 16) as u8;
+                *texel = [v, 255 - v, v, 255];
+            } else {
+                *texel = [200, 50, 10, 0];
+            }
+        }
+
+        let mut block = [0u8; 8];
+        write3_punchthrough(&rgba, 128, &mut block);
+
+        let mut decoded = RawColor4x4Buffer::default();
+        decompress(&block, true, &mut decoded);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let i = y * 4 + x;
+                let texel = decoded.texel(x, y);
+                if rgba[i][3] >= 128 {
+                    assert_eq!(texel[3], 255, "opaque texel {i} lost its alpha");
+                } else {
+                    assert_eq!(texel, [0, 0, 0, 0], "transparent texel {i} leaked colour/alpha");
+                }
+            }
+        }
+    }
+}
+
 /// Convert a little endian 565-packed colour to 8bpc RGBA
 fn unpack_565(packed: &[u8]) -> [u8; 4] {
     assert!(packed.len() == 2);
@@ -182,3 +494,80 @@ pub(crate) fn decompress(bytes: &[u8], is_bc1: bool, output: &mut impl RawColor4
         }
     }
 }
+
+/// Decompresses a run of consecutive BC1/2/3 colour blocks.
+///
+/// `bytes` must hold a whole number of 8-byte blocks and `out` must have
+/// exactly one entry per block. With the `simd` feature enabled, several
+/// blocks are decoded per call; otherwise this just calls [`decompress`]
+/// once per block.
+pub(crate) fn decompress_blocks(bytes: &[u8], is_bc1: bool, out: &mut [impl RawColor4x4Block]) {
+    assert_eq!(bytes.len() % 8, 0);
+    assert_eq!(bytes.len() / 8, out.len());
+
+    #[cfg(feature = "simd")]
+    {
+        crate::simd::decompress_blocks(bytes, is_bc1, out);
+    }
+
+    #[cfg(not(feature = "simd"))]
+    {
+        for (block_bytes, block_out) in bytes.chunks_exact(8).zip(out.iter_mut()) {
+            decompress(block_bytes, is_bc1, block_out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod decompress_blocks_tests {
+    use super::*;
+    use crate::RawColor4x4Buffer;
+
+    /// `decompress_blocks` (scalar or SIMD, depending on the `simd`
+    /// feature) must agree with decoding each block individually via
+    /// [`decompress`], for both BC1's 3-colour/punch-through mode and
+    /// its regular 4-colour mode. Uses 9 blocks (more than one SIMD
+    /// lane's worth, plus a remainder) so that the vectorized batch
+    /// path is actually exercised when the `simd` feature is enabled.
+    #[test]
+    fn agrees_with_decompress_per_block() {
+        // a <= b: BC1 3-colour + transparent-black mode.
+        let punchthrough_block: [u8; 8] = [0x00, 0x00, 0xFF, 0xFF, 0b11_10_01_00, 0, 0, 0];
+        // a > b: regular 4-colour mode.
+        let regular_block: [u8; 8] = [0xFF, 0xFF, 0x00, 0x00, 0b11_10_01_00, 0, 0, 0];
+
+        let single_blocks = [
+            punchthrough_block,
+            regular_block,
+            punchthrough_block,
+            regular_block,
+            punchthrough_block,
+            regular_block,
+            punchthrough_block,
+            regular_block,
+            punchthrough_block,
+        ];
+        let bytes: Vec<u8> = single_blocks.iter().flatten().copied().collect();
+
+        let mut batched = [RawColor4x4Buffer::default(); 9];
+        decompress_blocks(&bytes, true, &mut batched);
+
+        let mut individually = [RawColor4x4Buffer::default(); 9];
+        for (block, out) in single_blocks.iter().zip(individually.iter_mut()) {
+            decompress(block, true, out);
+        }
+
+        for block_index in 0..single_blocks.len() {
+            for y in 0..4 {
+                for x in 0..4 {
+                    for channel in 0..4 {
+                        assert_eq!(
+                            batched[block_index].texel(x, y)[channel],
+                            individually[block_index].texel(x, y)[channel],
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
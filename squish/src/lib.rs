@@ -0,0 +1,56 @@
+// Copyright (c) 2006 Simon Brown <si@sjbrown.co.uk>
+// Copyright (c) 2018-2021 Jan Solanti <jhs@psonet.com>
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to	deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+// SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! squish: a Rust port of libsquish, a DXT/BCn texture compression library.
+
+mod blockbuffer;
+mod colourblock;
+pub mod math;
+pub mod qoi;
+#[cfg(feature = "simd")]
+mod simd;
+
+pub use blockbuffer::{BlockBuffer, RawColor4x4Buffer};
+pub use colourblock::{
+    write3, write3_dithered, write3_punchthrough, write4, write4_dithered, DitherState,
+};
+
+/// A 4x4 block of raw colour texels that the BC block codecs read from
+/// or write into.
+pub trait RawColor4x4Block {
+    /// Number of colour channels stored per texel (e.g. 4 for RGBA).
+    fn number_of_channels(&self) -> usize;
+
+    /// Sets channel `channel` of the texel at `(x, y)` to `value`.
+    fn set_value(&mut self, x: usize, y: usize, channel: usize, value: u8);
+}
+
+/// Decompresses a single BC1/2/3 colour block into a 4x4 RGBA block.
+pub fn decompress(bytes: &[u8], is_bc1: bool, output: &mut impl RawColor4x4Block) {
+    colourblock::decompress(bytes, is_bc1, output)
+}
+
+/// Decompresses a run of consecutive BC1/2/3 colour blocks. See
+/// [`colourblock::decompress_blocks`] for details.
+pub fn decompress_blocks(bytes: &[u8], is_bc1: bool, out: &mut [impl RawColor4x4Block]) {
+    colourblock::decompress_blocks(bytes, is_bc1, out)
+}
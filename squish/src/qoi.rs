@@ -0,0 +1,305 @@
+// Copyright (c) 2006 Simon Brown <si@sjbrown.co.uk>
+// Copyright (c) 2018-2021 Jan Solanti <jhs@psonet.com>
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to	deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+// SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! A small, dependency-free lossless codec for the [QOI] image format.
+//!
+//! Unlike the BC block functions elsewhere in this crate, QOI is lossless,
+//! so it's useful as a way to persist an original RGBA image or a
+//! decompressed BC result for later exact comparison, without pulling in
+//! a full PNG dependency.
+//!
+//! [QOI]: https://qoiformat.org/qoi-specification.pdf
+
+const MAGIC: [u8; 4] = *b"qoif";
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const TAG_MASK: u8 = 0xc0;
+
+const RUNNING_ARRAY_LEN: usize = 64;
+
+/// Number of colour channels stored in the pixel data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channels {
+    Rgb = 3,
+    Rgba = 4,
+}
+
+/// The colourspace tag carried in the header. QOI does not interpret
+/// this; it's passed through for the caller's benefit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colorspace {
+    Srgb = 0,
+    Linear = 1,
+}
+
+/// Errors that can occur while decoding a QOI stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The stream is shorter than the 14-byte header.
+    TruncatedHeader,
+    /// The stream doesn't start with the `qoif` magic bytes.
+    BadMagic,
+    /// The header's channel count is neither 3 nor 4.
+    InvalidChannels(u8),
+    /// The tag stream ended before `width * height` pixels were decoded.
+    TruncatedData,
+}
+
+#[inline]
+fn hash_index(pixel: [u8; 4]) -> usize {
+    let [r, g, b, a] = pixel;
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % RUNNING_ARRAY_LEN
+}
+
+/// Encodes an RGBA image as a QOI stream.
+///
+/// `pixels` must contain exactly `width * height * 4` bytes.
+pub fn encode(pixels: &[u8], width: u32, height: u32, colorspace: Colorspace) -> Vec<u8> {
+    assert_eq!(pixels.len(), width as usize * height as usize * 4);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + pixels.len() + END_MARKER.len());
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.push(Channels::Rgba as u8);
+    out.push(colorspace as u8);
+
+    let mut running = [[0u8; 4]; RUNNING_ARRAY_LEN];
+    let mut previous = [0u8, 0u8, 0u8, 255u8];
+    let mut run: u32 = 0;
+
+    let emit_run = |out: &mut Vec<u8>, run: &mut u32| {
+        while *run > 0 {
+            let chunk = (*run).min(62);
+            out.push(OP_RUN | (chunk as u8 - 1));
+            *run -= chunk;
+        }
+    };
+
+    for pixel in pixels.chunks_exact(4) {
+        let pixel = [pixel[0], pixel[1], pixel[2], pixel[3]];
+
+        if pixel == previous {
+            run += 1;
+            if run == 62 {
+                emit_run(&mut out, &mut run);
+            }
+            continue;
+        }
+        emit_run(&mut out, &mut run);
+
+        let index = hash_index(pixel);
+        if running[index] == pixel {
+            out.push(OP_INDEX | index as u8);
+        } else {
+            running[index] = pixel;
+
+            let same_alpha = pixel[3] == previous[3];
+            let dr = pixel[0].wrapping_sub(previous[0]) as i8;
+            let dg = pixel[1].wrapping_sub(previous[1]) as i8;
+            let db = pixel[2].wrapping_sub(previous[2]) as i8;
+
+            if same_alpha && (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db)
+            {
+                out.push(
+                    OP_DIFF
+                        | (((dr + 2) as u8) << 4)
+                        | (((dg + 2) as u8) << 2)
+                        | ((db + 2) as u8),
+                );
+            } else {
+                let dr_dg = dr.wrapping_sub(dg);
+                let db_dg = db.wrapping_sub(dg);
+                if same_alpha
+                    && (-32..=31).contains(&dg)
+                    && (-8..=7).contains(&dr_dg)
+                    && (-8..=7).contains(&db_dg)
+                {
+                    out.push(OP_LUMA | ((dg + 32) as u8));
+                    out.push((((dr_dg + 8) as u8) << 4) | ((db_dg + 8) as u8));
+                } else if same_alpha {
+                    out.push(OP_RGB);
+                    out.extend_from_slice(&pixel[0..3]);
+                } else {
+                    out.push(OP_RGBA);
+                    out.extend_from_slice(&pixel);
+                }
+            }
+        }
+
+        previous = pixel;
+    }
+    emit_run(&mut out, &mut run);
+
+    out.extend_from_slice(&END_MARKER);
+    out
+}
+
+/// Decodes a QOI stream back into an image, returning the pixel data
+/// along with the width and height read from the header. The pixel data
+/// is RGBA, or RGB (3 bytes per pixel) when the header declares 3
+/// channels.
+pub fn decode(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), DecodeError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::TruncatedHeader);
+    }
+    if bytes[0..4] != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+
+    let width = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let height = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+    let channels = bytes[12];
+    if channels != 3 && channels != 4 {
+        return Err(DecodeError::InvalidChannels(channels));
+    }
+
+    let pixel_count = width as usize * height as usize;
+    let mut out = Vec::with_capacity(pixel_count * 4);
+
+    let mut running = [[0u8; 4]; RUNNING_ARRAY_LEN];
+    let mut previous = [0u8, 0u8, 0u8, 255u8];
+    let mut run: u32 = 0;
+
+    let mut cursor = HEADER_LEN;
+    while out.len() < pixel_count * 4 {
+        let pixel = if run > 0 {
+            run -= 1;
+            previous
+        } else {
+            if cursor >= bytes.len() {
+                return Err(DecodeError::TruncatedData);
+            }
+            let tag = bytes[cursor];
+            cursor += 1;
+
+            if tag == OP_RGB || tag == OP_RGBA {
+                if cursor + 3 > bytes.len() {
+                    return Err(DecodeError::TruncatedData);
+                }
+                let r = bytes[cursor];
+                let g = bytes[cursor + 1];
+                let b = bytes[cursor + 2];
+                cursor += 3;
+                let a = if tag == OP_RGBA {
+                    if cursor >= bytes.len() {
+                        return Err(DecodeError::TruncatedData);
+                    }
+                    let a = bytes[cursor];
+                    cursor += 1;
+                    a
+                } else {
+                    previous[3]
+                };
+                [r, g, b, a]
+            } else {
+                match tag & TAG_MASK {
+                    OP_INDEX => running[(tag & 0x3f) as usize],
+                    OP_DIFF => {
+                        let dr = ((tag >> 4) & 0x03) as i8 - 2;
+                        let dg = ((tag >> 2) & 0x03) as i8 - 2;
+                        let db = (tag & 0x03) as i8 - 2;
+                        [
+                            previous[0].wrapping_add(dr as u8),
+                            previous[1].wrapping_add(dg as u8),
+                            previous[2].wrapping_add(db as u8),
+                            previous[3],
+                        ]
+                    }
+                    OP_LUMA => {
+                        if cursor >= bytes.len() {
+                            return Err(DecodeError::TruncatedData);
+                        }
+                        let second = bytes[cursor];
+                        cursor += 1;
+                        let dg = (tag & 0x3f) as i8 - 32;
+                        let dr_dg = ((second >> 4) & 0x0f) as i8 - 8;
+                        let db_dg = (second & 0x0f) as i8 - 8;
+                        [
+                            previous[0].wrapping_add(dg.wrapping_add(dr_dg) as u8),
+                            previous[1].wrapping_add(dg as u8),
+                            previous[2].wrapping_add(dg.wrapping_add(db_dg) as u8),
+                            previous[3],
+                        ]
+                    }
+                    OP_RUN => {
+                        run = (tag & 0x3f) as u32;
+                        previous
+                    }
+                    _ => unreachable!("tag bits are exhaustively matched above"),
+                }
+            }
+        };
+
+        running[hash_index(pixel)] = pixel;
+        out.extend_from_slice(&pixel);
+        previous = pixel;
+    }
+
+    if channels == 3 {
+        out = out
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+    }
+
+    Ok((out, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_rgba_pixels() {
+        let width = 4;
+        let height = 3;
+        // A mix of a flat run, a gradient (luma ops), a repeated
+        // colour (index op) and varying alpha (forces RGBA ops).
+        let pixels: Vec<u8> = vec![
+            10, 10, 10, 255, 10, 10, 10, 255, 10, 10, 10, 255, 10, 10, 10, 255, //
+            11, 12, 13, 255, 12, 14, 16, 200, 10, 10, 10, 255, 0, 0, 0, 0, //
+            255, 0, 0, 128, 0, 255, 0, 64, 0, 0, 255, 32, 11, 12, 13, 255,
+        ];
+
+        let encoded = encode(&pixels, width, height, Colorspace::Srgb);
+        let (decoded, decoded_width, decoded_height) = decode(&encoded).unwrap();
+
+        assert_eq!(decoded_width, width);
+        assert_eq!(decoded_height, height);
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut encoded = encode(&[0, 0, 0, 0], 1, 1, Colorspace::Srgb);
+        encoded[0] = b'x';
+        assert_eq!(decode(&encoded), Err(DecodeError::BadMagic));
+    }
+}